@@ -5,6 +5,18 @@ use std::fs;
 use std::ffi::OsStr;
 use std::process::Command;
 
+mod history;
+mod logging;
+mod manifest;
+mod pipeline;
+mod reconcile;
+mod sign;
+
+pub use history::{read_history, rollback_last_transaction};
+pub use manifest::Manifest;
+pub use reconcile::{LoadedKpm, list_loaded_kpms, reconcile};
+pub use sign::{import_trusted_key, list_trusted_keys, remove_trusted_key};
+
 pub const KPM_DIR: &str = "/data/adb/kpm";
 pub const KPMMGR_PATH: &str = "/data/adb/ksu/bin/kpmmgr";
 
@@ -79,6 +91,9 @@ pub fn start_kpm_watcher() -> Result<()> {
         return Ok(());
     }
 
+    // 整个 watcher 会话内产生的事件归入同一个事务，便于整体回滚
+    history::begin_transaction();
+
     let mut watcher = notify::recommended_watcher(|res| {
         match res {
             Ok(event) => handle_kpm_event(event),
@@ -103,9 +118,8 @@ pub fn handle_kpm_event(event: notify::Event) {
 fn handle_create_event(paths: Vec<std::path::PathBuf>) {
     for path in paths {
         if path.extension() == Some(OsStr::new("kpm")) {
-            if let Err(e) = load_kpm(&path) {
-                log::warn!("Failed to load {}: {}", path.display(), e);
-            }
+            // 失败已经由 load_kpm 以结构化事件记录，这里无需再重复打印
+            let _ = load_kpm(&path);
         }
     }
 }
@@ -113,11 +127,26 @@ fn handle_create_event(paths: Vec<std::path::PathBuf>) {
 fn handle_remove_event(paths: Vec<std::path::PathBuf>) {
     for path in paths {
         if let Some(name) = path.file_stem().and_then(|s| s.to_str()) {
-            if let Err(e) = unload_kpm(name) {
-                log::warn!("Failed to unload {}: {}", name, e);
-            }
-            if let Err(e) = fs::remove_file(&path) {
-                log::error!("Failed to delete file: {}: {}", path.display(), e);
+            // 失败已经由 unload_kpm 以结构化事件记录
+            let _ = unload_kpm(name);
+
+            let event = logging::KpmEvent::new(logging::Op::Remove, name).path(path.to_string_lossy());
+            // 到这里文件通常已经被 watcher 报告的事件本身，或者 unload_kpm 顺手删除了；
+            // 只有在它仍然存在时才需要真的去删，否则会把早已完成的删除误记成失败
+            let removed = if path.exists() {
+                fs::remove_file(&path)
+            } else {
+                Ok(())
+            };
+            match removed {
+                Ok(()) => {
+                    history::record(history::Action::Remove, name, None, &path, true);
+                    event.emit();
+                }
+                Err(e) => {
+                    history::record(history::Action::Remove, name, None, &path, false);
+                    event.err(e.to_string()).emit();
+                }
             }
         }
     }
@@ -129,37 +158,67 @@ fn handle_modify_event(paths: Vec<std::path::PathBuf>) {
     }
 }
 
-// 加载 KPM 模块
+// 加载 KPM 模块：走 Configure → Verify → Backup → Commit → Finalize 的流水线
 pub fn load_kpm(path: &Path) -> Result<()> {
-    let path_str = path.to_str().ok_or_else(|| anyhow!("Invalid path: {}", path.display()))?;
-    let status = std::process::Command::new(KPMMGR_PATH)
-        .args(["load", path_str, ""])
-        .status()?;
+    // 非 UTF-8 路径也要走完流水线（Configure 会拒绝它），这样失败同样会被记录为结构化事件
+    let path_str = path.to_string_lossy();
+    let name = path.file_stem().and_then(|s| s.to_str()).unwrap_or(&path_str).to_string();
 
-    if status.success() {
-        log::info!("Loaded KPM: {}", path.display());
+    let result = pipeline::load(path);
+    let version = query_module_version(&name);
+    history::record(history::Action::Load, &name, version.clone(), path, result.is_ok());
+
+    let event = logging::KpmEvent::new(logging::Op::Load, &name).version(version).path(path_str);
+    match &result {
+        Ok(()) => event.emit(),
+        Err(e) => event.err(e.to_string()).emit(),
     }
-    Ok(())
+
+    result
+}
+
+// 查询某个已加载模块解析出的版本号
+fn query_module_version(name: &str) -> Option<String> {
+    let output = Command::new(KPMMGR_PATH).args(["info", name]).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let version = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (!version.is_empty()).then_some(version)
 }
 
 // 卸载 KPM 模块并尝试删除对应文件
 pub fn unload_kpm(name: &str) -> Result<()> {
-    let status = std::process::Command::new(KPMMGR_PATH)
-        .args(["unload", name])
-        .status()
-        .map_err(|e| anyhow!("Failed to execute kpmmgr: {}", e))?;
+    // 必须在调用 kpmmgr unload 之前解析版本号，模块卸载后就查不到了
+    let version = query_module_version(name);
+
+    let status = match std::process::Command::new(KPMMGR_PATH).args(["unload", name]).status() {
+        Ok(status) => status,
+        Err(e) => {
+            let reason = format!("Failed to execute kpmmgr: {e}");
+            logging::KpmEvent::new(logging::Op::Unload, name).version(version).err(reason.clone()).emit();
+            return Err(anyhow!(reason));
+        }
+    };
+
+    let kpm_path = find_kpm_file(name)?.unwrap_or_else(|| Path::new(KPM_DIR).join(format!("{name}.kpm")));
+    let event = logging::KpmEvent::new(logging::Op::Unload, name)
+        .version(version.clone())
+        .path(kpm_path.to_string_lossy());
 
     if status.success() {
-        let kpm_path = find_kpm_file(name)?;
-        if let Some(path) = kpm_path {
-            fs::remove_file(&path)
-                .map_err(|e| anyhow!("Failed to delete KPM file: {}: {}", path.display(), e))?;
-            log::info!("Deleted KPM file: {}", path.display());
+        // 记录历史（并在文件仍存在时备份）必须先于删除文件，否则回滚时没有备份可用
+        history::record(history::Action::Unload, name, version, &kpm_path, true);
+
+        if kpm_path.exists() {
+            fs::remove_file(&kpm_path)
+                .map_err(|e| anyhow!("Failed to delete KPM file: {}: {}", kpm_path.display(), e))?;
         }
 
-        log::info!("Successfully unloaded KPM: {}", name);
+        event.emit();
     } else {
-        log::warn!("KPM unloading may have failed: {}", name);
+        history::record(history::Action::Unload, name, version, &kpm_path, false);
+        event.err("kpmmgr reported unload failure").emit();
     }
 
     Ok(())
@@ -185,46 +244,56 @@ fn find_kpm_file(name: &str) -> Result<Option<std::path::PathBuf>> {
     Ok(None)
 }
 
-// 安全模式下删除所有 KPM 模块
+// 安全模式下删除所有 KPM 模块，依赖方先于被依赖方下线
 pub fn remove_all_kpms() -> Result<()> {
     ensure_kpm_dir()?;
+    history::begin_transaction();
 
+    // 依赖无法解析（缺失依赖、清单读取失败或存在环）的模块不在排序结果中，排在最后同样删除
+    let mut paths = manifest::resolve_unload_order().unwrap_or_else(|e| {
+        log::warn!("Failed to resolve KPM unload order: {}", e);
+        Vec::new()
+    });
     for entry in fs::read_dir(KPM_DIR)? {
         let path = entry?.path();
-        if path.extension().is_some_and(|ext| ext == "kpm") {
-            if let Some(name) = path.file_stem() {
-                if let Err(e) = unload_kpm(name.to_string_lossy().as_ref()) {
-                    log::error!("Failed to remove KPM: {}", e);
-                }
-                if let Err(e) = fs::remove_file(&path) {
-                    log::error!("Failed to delete file: {}: {}", path.display(), e);
-                }
+        if path.extension().is_some_and(|ext| ext == "kpm") && !paths.contains(&path) {
+            paths.push(path);
+        }
+    }
+
+    for path in paths {
+        let Some(name) = path.file_stem().map(|n| n.to_string_lossy().to_string()) else {
+            continue;
+        };
+        if let Err(e) = unload_kpm(&name) {
+            log::error!("Failed to remove KPM: {}", e);
+        }
+
+        // unload_kpm 只在卸载成功时删除文件；这里只需要在它还残留时兜底清理，
+        // 否则把早已完成的删除误记成失败
+        if !path.exists() {
+            continue;
+        }
+        match fs::remove_file(&path) {
+            Ok(()) => history::record(history::Action::Remove, &name, None, &path, true),
+            Err(e) => {
+                log::error!("Failed to delete file: {}: {}", path.display(), e);
+                history::record(history::Action::Remove, &name, None, &path, false);
             }
         }
     }
     Ok(())
 }
 
-// 加载 KPM 模块
+// 加载 KPM 模块，按清单中声明的依赖关系解析出的顺序依次加载
 pub fn load_kpm_modules() -> Result<()> {
     ensure_kpm_dir()?;
+    history::begin_transaction();
 
-    for entry in std::fs::read_dir(KPM_DIR)? {
-        let path = entry?.path();
-        if let Some(file_name) = path.file_stem() {
-            if let Some(file_name_str) = file_name.to_str() {
-                if file_name_str.is_empty() {
-                    log::warn!("Invalid KPM file name: {}", path.display());
-                    continue;
-                }
-            }
-        }
-    
-        if path.extension().is_some_and(|ext| ext == "kpm") {
-            match load_kpm(&path) {
-                Ok(()) => log::info!("Successfully loaded KPM module: {}", path.display()),
-                Err(e) => log::warn!("Failed to load KPM module {}: {}", path.display(), e),
-            }
+    for path in manifest::resolve_load_order()? {
+        match load_kpm(&path) {
+            Ok(()) => log::info!("Successfully loaded KPM module: {}", path.display()),
+            Err(e) => log::warn!("Failed to load KPM module {}: {}", path.display(), e),
         }
     }
 
@@ -0,0 +1,170 @@
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// JSON-lines 形式的操作历史
+pub const HISTORY_PATH: &str = "/data/adb/kpm/history.log";
+// 回滚时用来恢复被卸载/移除模块的备份目录
+const BACKUP_DIR: &str = "/data/adb/kpm/history-backups";
+
+static NEXT_TRANSACTION_ID: AtomicU64 = AtomicU64::new(1);
+static CURRENT_TRANSACTION_ID: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum Action {
+    Load,
+    Unload,
+    Remove,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub timestamp: u64,
+    pub transaction_id: u64,
+    pub module: String,
+    pub version: Option<String>,
+    pub source_path: String,
+    pub action: Action,
+    pub success: bool,
+}
+
+// 为一个 watcher 会话 / 一批操作分配新的事务 id，同一事务内的事件共享该 id
+pub fn begin_transaction() -> u64 {
+    let id = NEXT_TRANSACTION_ID.fetch_add(1, Ordering::SeqCst);
+    CURRENT_TRANSACTION_ID.store(id, Ordering::SeqCst);
+    id
+}
+
+fn current_transaction() -> u64 {
+    CURRENT_TRANSACTION_ID.load(Ordering::SeqCst)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+// 记录一次 load/unload/remove 操作
+pub fn record(action: Action, module: &str, version: Option<String>, source_path: &Path, success: bool) {
+    if success && matches!(action, Action::Unload | Action::Remove) {
+        if let Err(e) = backup_module(source_path) {
+            log::warn!("Failed to back up {} before {:?}: {}", source_path.display(), action, e);
+        }
+    }
+
+    let entry = HistoryEntry {
+        timestamp: now(),
+        transaction_id: current_transaction(),
+        module: module.to_string(),
+        version,
+        source_path: source_path.to_string_lossy().to_string(),
+        action,
+        success,
+    };
+
+    if let Err(e) = append_entry(&entry) {
+        log::warn!("Failed to write KPM history entry: {}", e);
+    }
+}
+
+fn append_entry(entry: &HistoryEntry) -> Result<()> {
+    if let Some(parent) = Path::new(HISTORY_PATH).parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let line = serde_json::to_string(entry)
+        .map_err(|e| anyhow!("Failed to serialize history entry: {}", e))?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(HISTORY_PATH)?;
+    writeln!(file, "{line}")?;
+    Ok(())
+}
+
+// 读取完整的历史记录，供 UI/CLI 使用
+pub fn read_history() -> Result<Vec<HistoryEntry>> {
+    if !Path::new(HISTORY_PATH).exists() {
+        return Ok(Vec::new());
+    }
+
+    let content = fs::read_to_string(HISTORY_PATH)?;
+    let mut entries = Vec::new();
+    for line in content.lines().filter(|l| !l.trim().is_empty()) {
+        match serde_json::from_str::<HistoryEntry>(line) {
+            Ok(entry) => entries.push(entry),
+            Err(e) => log::warn!("Skipping malformed history entry: {}", e),
+        }
+    }
+    Ok(entries)
+}
+
+fn backup_path_for(source_path: &Path) -> PathBuf {
+    let name = source_path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "unknown.kpm".to_string());
+    Path::new(BACKUP_DIR).join(name)
+}
+
+fn backup_module(source_path: &Path) -> Result<()> {
+    if !source_path.exists() {
+        return Ok(());
+    }
+    fs::create_dir_all(BACKUP_DIR)?;
+    fs::copy(source_path, backup_path_for(source_path))?;
+    Ok(())
+}
+
+// 回滚最近一个事务：按相反顺序撤销该事务内记录的所有成功操作
+pub fn rollback_last_transaction() -> Result<()> {
+    let entries = read_history()?;
+    let Some(last_txn) = entries.iter().rev().find(|e| e.success).map(|e| e.transaction_id) else {
+        return Err(anyhow!("No transaction to roll back"));
+    };
+
+    let mut txn_entries: Vec<_> = entries
+        .iter()
+        .filter(|e| e.transaction_id == last_txn && e.success)
+        .collect();
+    txn_entries.reverse();
+
+    // 撤销动作本身也会调用 unload_kpm/load_kpm 从而写入新的历史记录；
+    // 必须先开启一个新事务，否则这些记录会被归入正在回滚的那个事务，
+    // 导致下一次回滚把刚刚的撤销动作也再回滚一遍。
+    begin_transaction();
+
+    for entry in txn_entries {
+        match entry.action {
+            Action::Load => {
+                log::info!("Rolling back load of {}: unloading", entry.module);
+                if let Err(e) = super::unload_kpm(&entry.module) {
+                    log::warn!("Rollback: failed to unload {}: {}", entry.module, e);
+                }
+            }
+            Action::Unload | Action::Remove => {
+                let source_path = Path::new(&entry.source_path);
+                let backup = backup_path_for(source_path);
+                if !backup.exists() {
+                    log::warn!("Rollback: no backup available for {}, skipping", entry.module);
+                    continue;
+                }
+                if let Err(e) = fs::copy(&backup, source_path) {
+                    log::warn!("Rollback: failed to restore {}: {}", entry.module, e);
+                    continue;
+                }
+                log::info!("Rolling back {:?} of {}: reloading from backup", entry.action, entry.module);
+                if let Err(e) = super::load_kpm(source_path) {
+                    log::warn!("Rollback: failed to reload {}: {}", entry.module, e);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,100 @@
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Op {
+    Load,
+    Unload,
+    Remove,
+}
+
+impl Op {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Op::Load => "load",
+            Op::Unload => "unload",
+            Op::Remove => "remove",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Outcome {
+    Ok,
+    Err,
+}
+
+// 一条结构化的 KPM 事件：既能渲染为 key=value 的人类可读行，也能序列化为 JSON
+#[derive(Debug, Serialize)]
+pub struct KpmEvent {
+    module: String,
+    version: Option<String>,
+    path: Option<String>,
+    op: Op,
+    result: Outcome,
+    reason: Option<String>,
+}
+
+impl KpmEvent {
+    pub fn new(op: Op, module: impl Into<String>) -> Self {
+        Self {
+            module: module.into(),
+            version: None,
+            path: None,
+            op,
+            result: Outcome::Ok,
+            reason: None,
+        }
+    }
+
+    pub fn version(mut self, version: Option<String>) -> Self {
+        self.version = version;
+        self
+    }
+
+    pub fn path(mut self, path: impl Into<String>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    pub fn err(mut self, reason: impl Into<String>) -> Self {
+        self.result = Outcome::Err;
+        self.reason = Some(reason.into());
+        self
+    }
+
+    fn render(&self) -> String {
+        let mut parts = vec![format!("module={}", self.module)];
+        if let Some(version) = &self.version {
+            parts.push(format!("version={version}"));
+        }
+        if let Some(path) = &self.path {
+            parts.push(format!("path={path}"));
+        }
+        parts.push(format!("op={}", self.op.as_str()));
+        parts.push(format!("result={}", match self.result {
+            Outcome::Ok => "ok",
+            Outcome::Err => "err",
+        }));
+        if let Some(reason) = &self.reason {
+            parts.push(format!("reason={reason}"));
+        }
+        parts.join(" ")
+    }
+
+    // JSON 形式，供日志采集端/管理端 UI 解析
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+
+    // 输出人类可读行（info/warn 取决于结果），并在 trace 级别附带 JSON
+    pub fn emit(self) {
+        let line = self.render();
+        match self.result {
+            Outcome::Ok => log::info!("{line}"),
+            Outcome::Err => log::warn!("{line}"),
+        }
+        log::trace!("{}", self.to_json());
+    }
+}
@@ -0,0 +1,230 @@
+use anyhow::{Result, anyhow};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::KPM_DIR;
+
+// `<name>.kpm.toml` 中的 `[package]` 块
+#[derive(Debug, Clone, Deserialize)]
+pub struct Manifest {
+    pub name: String,
+    pub version: Option<String>,
+    #[serde(default)]
+    pub requires: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ManifestFile {
+    package: Manifest,
+}
+
+// 读取某个 .kpm 文件的 sidecar 清单；不存在则视为以文件名（去掉 .kpm）命名、无依赖的模块
+pub fn read_manifest(kpm_path: &Path) -> Result<Manifest> {
+    let manifest_path = sidecar_path(kpm_path);
+    let name = kpm_path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or_default()
+        .to_string();
+
+    if !manifest_path.exists() {
+        return Ok(Manifest { name, version: None, requires: Vec::new() });
+    }
+
+    let text = fs::read_to_string(&manifest_path)
+        .map_err(|e| anyhow!("Failed to read manifest {}: {}", manifest_path.display(), e))?;
+    let parsed: ManifestFile = toml::from_str(&text)
+        .map_err(|e| anyhow!("Failed to parse manifest {}: {}", manifest_path.display(), e))?;
+    Ok(parsed.package)
+}
+
+fn sidecar_path(kpm_path: &Path) -> PathBuf {
+    let mut name = kpm_path.as_os_str().to_os_string();
+    name.push(".toml");
+    PathBuf::from(name)
+}
+
+fn discover_modules() -> Result<Vec<(Manifest, PathBuf)>> {
+    let mut modules = Vec::new();
+    if !Path::new(KPM_DIR).exists() {
+        return Ok(modules);
+    }
+
+    for entry in fs::read_dir(KPM_DIR)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "kpm") {
+            match read_manifest(&path) {
+                Ok(manifest) => modules.push((manifest, path)),
+                Err(e) => log::warn!("Failed to read manifest for {}: {}", path.display(), e),
+            }
+        }
+    }
+    Ok(modules)
+}
+
+// 按依赖关系解析出加载顺序：前置模块排在依赖它们的模块之前
+pub fn resolve_load_order() -> Result<Vec<PathBuf>> {
+    topo_sort(discover_modules()?)
+}
+
+// 卸载顺序与加载顺序相反，保证依赖方先于被依赖方下线
+pub fn resolve_unload_order() -> Result<Vec<PathBuf>> {
+    let mut order = resolve_load_order()?;
+    order.reverse();
+    Ok(order)
+}
+
+fn topo_sort(modules: Vec<(Manifest, PathBuf)>) -> Result<Vec<PathBuf>> {
+    let mut by_name: HashMap<String, (Manifest, PathBuf)> = modules
+        .into_iter()
+        .map(|(manifest, path)| (manifest.name.clone(), (manifest, path)))
+        .collect();
+
+    // 级联剔除缺失依赖（以及依赖于被剔除模块）的模块，而不是让它们拖垮整体解析
+    let mut skipped: HashSet<String> = HashSet::new();
+    loop {
+        let mut newly_skipped = Vec::new();
+        for (name, (manifest, _)) in &by_name {
+            if skipped.contains(name) {
+                continue;
+            }
+            for dep in &manifest.requires {
+                let missing = !by_name.contains_key(dep);
+                let blocked = skipped.contains(dep);
+                if missing {
+                    log::warn!("Skipping {}: requires missing module {}", name, dep);
+                    newly_skipped.push(name.clone());
+                    break;
+                }
+                if blocked {
+                    log::warn!("Skipping {}: depends on skipped module {}", name, dep);
+                    newly_skipped.push(name.clone());
+                    break;
+                }
+            }
+        }
+        if newly_skipped.is_empty() {
+            break;
+        }
+        skipped.extend(newly_skipped);
+    }
+    for name in &skipped {
+        by_name.remove(name);
+    }
+
+    // 剩余模块上的拓扑排序；若存在环则直接中止并报出具体的环
+    let mut names: Vec<String> = by_name.keys().cloned().collect();
+    names.sort();
+
+    let mut order = Vec::new();
+    let mut visited = HashSet::new();
+    let mut visiting = Vec::new();
+    for name in &names {
+        visit(name, &by_name, &mut visited, &mut visiting, &mut order)?;
+    }
+
+    Ok(order.into_iter().map(|name| by_name[&name].1.clone()).collect())
+}
+
+fn visit(
+    name: &str,
+    by_name: &HashMap<String, (Manifest, PathBuf)>,
+    visited: &mut HashSet<String>,
+    visiting: &mut Vec<String>,
+    order: &mut Vec<String>,
+) -> Result<()> {
+    if visited.contains(name) {
+        return Ok(());
+    }
+    if let Some(pos) = visiting.iter().position(|n| n == name) {
+        let mut cycle = visiting[pos..].to_vec();
+        cycle.push(name.to_string());
+        return Err(anyhow!("Dependency cycle detected: {}", cycle.join(" -> ")));
+    }
+
+    let Some((manifest, _)) = by_name.get(name) else {
+        return Ok(());
+    };
+
+    visiting.push(name.to_string());
+    for dep in &manifest.requires {
+        visit(dep, by_name, visited, visiting, order)?;
+    }
+    visiting.pop();
+
+    visited.insert(name.to_string());
+    order.push(name.to_string());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn module(name: &str, requires: &[&str]) -> (Manifest, PathBuf) {
+        let manifest = Manifest {
+            name: name.to_string(),
+            version: None,
+            requires: requires.iter().map(|s| s.to_string()).collect(),
+        };
+        (manifest, PathBuf::from(format!("/data/adb/kpm/{name}.kpm")))
+    }
+
+    fn names(paths: &[PathBuf]) -> Vec<String> {
+        paths
+            .iter()
+            .map(|p| p.file_stem().unwrap().to_str().unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn orders_prerequisites_before_dependents() {
+        let modules = vec![module("b", &["a"]), module("a", &[])];
+        let order = names(&topo_sort(modules).unwrap());
+        assert_eq!(order, vec!["a", "b"]);
+    }
+
+    #[test]
+    fn resolves_a_diamond_dependency_once_each() {
+        let modules = vec![
+            module("d", &["b", "c"]),
+            module("b", &["a"]),
+            module("c", &["a"]),
+            module("a", &[]),
+        ];
+        let order = names(&topo_sort(modules).unwrap());
+        assert_eq!(order.len(), 4);
+        assert!(order.iter().position(|n| n == "a").unwrap() < order.iter().position(|n| n == "b").unwrap());
+        assert!(order.iter().position(|n| n == "a").unwrap() < order.iter().position(|n| n == "c").unwrap());
+        assert!(order.iter().position(|n| n == "b").unwrap() < order.iter().position(|n| n == "d").unwrap());
+        assert!(order.iter().position(|n| n == "c").unwrap() < order.iter().position(|n| n == "d").unwrap());
+    }
+
+    #[test]
+    fn detects_a_dependency_cycle() {
+        let modules = vec![module("a", &["b"]), module("b", &["a"])];
+        let err = topo_sort(modules).unwrap_err();
+        assert!(err.to_string().contains("cycle"));
+    }
+
+    #[test]
+    fn skips_a_module_with_a_missing_dependency() {
+        let modules = vec![module("a", &["missing"]), module("b", &[])];
+        let order = names(&topo_sort(modules).unwrap());
+        assert_eq!(order, vec!["b"]);
+    }
+
+    #[test]
+    fn cascades_skip_to_modules_depending_on_a_skipped_one() {
+        // c -> b -> missing: both b and c should be skipped, unrelated d still loads
+        let modules = vec![
+            module("c", &["b"]),
+            module("b", &["missing"]),
+            module("d", &[]),
+        ];
+        let order = names(&topo_sort(modules).unwrap());
+        assert_eq!(order, vec!["d"]);
+    }
+}
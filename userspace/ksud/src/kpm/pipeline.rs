@@ -0,0 +1,146 @@
+use anyhow::{Result, anyhow};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use super::{KPMMGR_PATH, manifest, sign};
+
+// 上一次成功加载并 Finalize 过的模块字节快照，供下一次加载失败时回退
+const SNAPSHOT_DIR: &str = "/data/adb/kpm/.snapshots";
+
+// Configure → Verify → Backup → Commit → Finalize 的加载流水线上下文
+struct PipelineContext {
+    path: PathBuf,
+    name: String,
+    backup_path: Option<PathBuf>,
+}
+
+// 以事务化的方式加载一个 KPM 模块：任一阶段失败都会撤销已完成的阶段
+pub fn load(path: &Path) -> Result<()> {
+    let mut ctx = configure(path)?;
+    verify(&ctx)?;
+    backup(&mut ctx)?;
+
+    if let Err(e) = commit(&ctx) {
+        undo(&ctx, false);
+        return Err(e);
+    }
+
+    if let Err(e) = finalize(&ctx) {
+        undo(&ctx, true);
+        return Err(e);
+    }
+
+    if let Some(backup_path) = &ctx.backup_path {
+        let _ = fs::remove_file(backup_path);
+    }
+    if let Err(e) = snapshot(&ctx) {
+        log::warn!("Failed to snapshot {} after successful load: {}", ctx.name, e);
+    }
+    Ok(())
+}
+
+// Configure：解析路径并读取元数据
+fn configure(path: &Path) -> Result<PipelineContext> {
+    if !path.exists() {
+        return Err(anyhow!("KPM file does not exist: {}", path.display()));
+    }
+    let name = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .ok_or_else(|| anyhow!("Invalid KPM file name: {}", path.display()))?
+        .to_string();
+
+    // 读取清单仅用于记录依赖信息，实际的加载顺序已由调用方解析好
+    if let Ok(manifest) = manifest::read_manifest(path) {
+        if !manifest.requires.is_empty() {
+            log::info!("{} declares dependencies: {}", name, manifest.requires.join(", "));
+        }
+    }
+
+    Ok(PipelineContext { path: path.to_path_buf(), name, backup_path: None })
+}
+
+// Verify：运行签名/格式检查
+fn verify(ctx: &PipelineContext) -> Result<()> {
+    let outcome = sign::verify_kpm(&ctx.path)?;
+    if !outcome.allows_load() {
+        return Err(anyhow!("Refusing to load untrusted or unsigned KPM: {}", ctx.name));
+    }
+    Ok(())
+}
+
+// Backup：若存在上一次成功加载过的快照，把它复制出来备用，以便覆盖失败时回退。
+// 注意不能从 `ctx.path` 备份——watcher 触发加载时，该路径上已经是这次要加载的新内容了。
+fn backup(ctx: &mut PipelineContext) -> Result<()> {
+    let snapshot_path = snapshot_path(&ctx.name);
+    if !snapshot_path.exists() {
+        return Ok(());
+    }
+
+    let backup_path = PathBuf::from(format!("{}.bak", ctx.path.display()));
+    fs::copy(&snapshot_path, &backup_path)
+        .map_err(|e| anyhow!("Failed to back up {} before reload: {}", ctx.name, e))?;
+    ctx.backup_path = Some(backup_path);
+    Ok(())
+}
+
+fn snapshot_path(name: &str) -> PathBuf {
+    Path::new(SNAPSHOT_DIR).join(format!("{name}.kpm"))
+}
+
+// 在一次加载成功并通过 Finalize 确认后，把当前字节记为"已知良好"的快照
+fn snapshot(ctx: &PipelineContext) -> Result<()> {
+    fs::create_dir_all(SNAPSHOT_DIR)?;
+    fs::copy(&ctx.path, snapshot_path(&ctx.name))?;
+    Ok(())
+}
+
+// Commit：调用 kpmmgr 实际加载模块
+fn commit(ctx: &PipelineContext) -> Result<()> {
+    let path_str = ctx
+        .path
+        .to_str()
+        .ok_or_else(|| anyhow!("Invalid path: {}", ctx.path.display()))?;
+    let status = Command::new(KPMMGR_PATH).args(["load", path_str, ""]).status()?;
+    if !status.success() {
+        return Err(anyhow!("kpmmgr load failed for {}", ctx.name));
+    }
+    Ok(())
+}
+
+// Finalize：向 kpmmgr 确认模块确实已经生效
+fn finalize(ctx: &PipelineContext) -> Result<()> {
+    if super::query_module_version(&ctx.name).is_none() {
+        return Err(anyhow!("{} did not come up after loading", ctx.name));
+    }
+    Ok(())
+}
+
+// 撤销已完成的阶段：卸载部分加载的模块，并从备份恢复原来的文件
+fn undo(ctx: &PipelineContext, unload_partial: bool) {
+    if unload_partial {
+        if let Err(e) = Command::new(KPMMGR_PATH).args(["unload", &ctx.name]).status() {
+            log::warn!("Rollback: failed to unload partial module {}: {}", ctx.name, e);
+        }
+    }
+
+    let Some(backup_path) = &ctx.backup_path else {
+        return;
+    };
+
+    if let Err(e) = fs::copy(backup_path, &ctx.path) {
+        log::error!("Rollback: failed to restore backup for {}: {}", ctx.name, e);
+        return;
+    }
+    let _ = fs::remove_file(backup_path);
+
+    let path_str = ctx.path.to_string_lossy().to_string();
+    match Command::new(KPMMGR_PATH).args(["load", &path_str, ""]).status() {
+        Ok(status) if status.success() => {
+            log::info!("Rollback: restored previous version of {} from backup", ctx.name);
+        }
+        Ok(_) => log::error!("Rollback: failed to reload backed-up version of {}", ctx.name),
+        Err(e) => log::error!("Rollback: failed to reload backed-up version of {}: {}", ctx.name, e),
+    }
+}
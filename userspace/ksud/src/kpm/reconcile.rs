@@ -0,0 +1,93 @@
+use anyhow::{Result, anyhow};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use super::{KPM_DIR, KPMMGR_PATH, load_kpm, manifest, unload_kpm};
+
+// kpmmgr 报告的一个已加载模块
+#[derive(Debug, Clone)]
+pub struct LoadedKpm {
+    pub name: String,
+    pub version: Option<String>,
+}
+
+// 通过 kpmmgr 枚举当前已加载的所有模块
+pub fn list_loaded_kpms() -> Result<Vec<LoadedKpm>> {
+    let output = Command::new(KPMMGR_PATH)
+        .arg("list")
+        .output()
+        .map_err(|e| anyhow!("Failed to execute kpmmgr: {}", e))?;
+
+    if !output.status.success() {
+        let error = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(anyhow!("Error listing loaded KPMs: {}", error));
+    }
+
+    let text = String::from_utf8_lossy(&output.stdout);
+    Ok(text.lines().filter_map(parse_loaded_line).collect())
+}
+
+// `kpmmgr list` 每行形如 "name version" 或仅 "name"
+fn parse_loaded_line(line: &str) -> Option<LoadedKpm> {
+    let line = line.trim();
+    if line.is_empty() {
+        return None;
+    }
+    let mut parts = line.split_whitespace();
+    let name = parts.next()?.to_string();
+    let version = parts.next().map(|s| s.to_string());
+    Some(LoadedKpm { name, version })
+}
+
+fn discover_on_disk() -> Result<Vec<(String, PathBuf)>> {
+    let mut modules = Vec::new();
+    if !Path::new(KPM_DIR).exists() {
+        return Ok(modules);
+    }
+    for entry in fs::read_dir(KPM_DIR)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "kpm") {
+            if let Some(name) = path.file_stem().and_then(|s| s.to_str()) {
+                modules.push((name.to_string(), path));
+            }
+        }
+    }
+    Ok(modules)
+}
+
+// 让实际加载状态与 KPM_DIR 中的文件保持一致：
+// - 磁盘上有但未加载的模块会按依赖顺序补齐加载
+// - 若 `unload_orphans` 为真，已加载但文件已在 watcher 之外被删除的模块会被卸载
+// 可以在启动时调用一次，弥补 watcher 未运行期间发生的变化
+pub fn reconcile(unload_orphans: bool) -> Result<()> {
+    let loaded_names: HashSet<String> = list_loaded_kpms()?.into_iter().map(|m| m.name).collect();
+    let on_disk_names: HashSet<String> = discover_on_disk()?.into_iter().map(|(name, _)| name).collect();
+
+    for path in manifest::resolve_load_order()? {
+        let Some(name) = path.file_stem().and_then(|s| s.to_str()) else {
+            continue;
+        };
+        if loaded_names.contains(name) {
+            continue;
+        }
+        log::info!("Reconcile: loading {} which is on disk but not loaded", name);
+        if let Err(e) = load_kpm(&path) {
+            log::warn!("Reconcile: failed to load {}: {}", name, e);
+        }
+    }
+
+    if unload_orphans {
+        for name in &loaded_names {
+            if !on_disk_names.contains(name) {
+                log::info!("Reconcile: unloading {} whose file was removed out-of-band", name);
+                if let Err(e) = unload_kpm(name) {
+                    log::warn!("Reconcile: failed to unload {}: {}", name, e);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,250 @@
+use anyhow::{Result, anyhow};
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// 受信任公钥存放目录
+pub const TRUSTED_KEYS_DIR: &str = "/data/adb/kpm/trusted-keys";
+// 存在该文件时，允许加载未签名的 KPM（仅用于调试）
+const ALLOW_UNSIGNED_FLAG: &str = "/data/adb/kpm/allow-unsigned";
+
+// 单次验证的结果
+pub enum VerifyOutcome {
+    // 验证通过，记录匹配上的公钥 id（文件名）
+    Trusted { key_id: String },
+    // 没有找到签名文件
+    Unsigned,
+    // 存在签名但没有任何受信任的公钥能验证通过
+    Untrusted,
+}
+
+impl VerifyOutcome {
+    // 结合 "允许未签名" 开关，判断这个结果是否允许继续加载
+    pub fn allows_load(&self) -> bool {
+        match self {
+            VerifyOutcome::Trusted { .. } => true,
+            VerifyOutcome::Unsigned => unsigned_permitted(),
+            VerifyOutcome::Untrusted => false,
+        }
+    }
+}
+
+// 是否允许在没有签名的情况下加载（调试用开关）
+pub fn unsigned_permitted() -> bool {
+    Path::new(ALLOW_UNSIGNED_FLAG).exists()
+}
+
+// 对一个 .kpm 文件执行签名验证
+pub fn verify_kpm(path: &Path) -> Result<VerifyOutcome> {
+    verify_kpm_against(path, Path::new(TRUSTED_KEYS_DIR))
+}
+
+// 实际校验逻辑，keys_dir 可注入以便测试；生产代码只通过 verify_kpm 调用
+fn verify_kpm_against(path: &Path, keys_dir: &Path) -> Result<VerifyOutcome> {
+    let Some(sig_path) = find_signature_file(path) else {
+        return Ok(VerifyOutcome::Unsigned);
+    };
+
+    let digest = hash_module(path)?;
+    let signature_bytes = fs::read(&sig_path)
+        .map_err(|e| anyhow!("Failed to read signature {}: {}", sig_path.display(), e))?;
+    let signature = Signature::from_slice(&signature_bytes)
+        .map_err(|e| anyhow!("Malformed signature {}: {}", sig_path.display(), e))?;
+
+    for (key_id, key) in list_trusted_key_material_in(keys_dir)? {
+        if key.verify(&digest, &signature).is_ok() {
+            return Ok(VerifyOutcome::Trusted { key_id });
+        }
+    }
+
+    Ok(VerifyOutcome::Untrusted)
+}
+
+// 查找配套的签名文件：<name>.kpm.sig
+fn find_signature_file(path: &Path) -> Option<PathBuf> {
+    let sig_path = PathBuf::from(format!("{}.sig", path.display()));
+    sig_path.exists().then_some(sig_path)
+}
+
+// 计算模块文件的 sha256 摘要
+fn hash_module(path: &Path) -> Result<[u8; 32]> {
+    let data = fs::read(path)
+        .map_err(|e| anyhow!("Failed to read module {}: {}", path.display(), e))?;
+    Ok(Sha256::digest(&data).into())
+}
+
+// 读取 trusted-keys 目录下所有公钥
+fn list_trusted_key_material() -> Result<Vec<(String, VerifyingKey)>> {
+    list_trusted_key_material_in(Path::new(TRUSTED_KEYS_DIR))
+}
+
+fn list_trusted_key_material_in(dir: &Path) -> Result<Vec<(String, VerifyingKey)>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut keys = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "pub") {
+            let Some(key_id) = path.file_stem().and_then(|s| s.to_str()) else {
+                continue;
+            };
+            let bytes = fs::read(&path)?;
+            let Ok(bytes): Result<[u8; 32], _> = bytes.try_into() else {
+                log::warn!("Skipping malformed trusted key: {}", path.display());
+                continue;
+            };
+            match VerifyingKey::from_bytes(&bytes) {
+                Ok(key) => keys.push((key_id.to_string(), key)),
+                Err(e) => log::warn!("Skipping invalid trusted key {}: {}", path.display(), e),
+            }
+        }
+    }
+    Ok(keys)
+}
+
+// 导入一个公钥到受信任的 keyring
+pub fn import_trusted_key(key_id: &str, path: &Path) -> Result<()> {
+    fs::create_dir_all(TRUSTED_KEYS_DIR)?;
+    let dest = Path::new(TRUSTED_KEYS_DIR).join(format!("{key_id}.pub"));
+    fs::copy(path, &dest)
+        .map_err(|e| anyhow!("Failed to import trusted key {}: {}", path.display(), e))?;
+    log::info!("Imported trusted key: {}", key_id);
+    Ok(())
+}
+
+// 列出受信任的公钥 id
+pub fn list_trusted_keys() -> Result<Vec<String>> {
+    Ok(list_trusted_key_material()?
+        .into_iter()
+        .map(|(key_id, _)| key_id)
+        .collect())
+}
+
+// 从 keyring 中移除一个公钥
+pub fn remove_trusted_key(key_id: &str) -> Result<()> {
+    let path = Path::new(TRUSTED_KEYS_DIR).join(format!("{key_id}.pub"));
+    fs::remove_file(&path)
+        .map_err(|e| anyhow!("Failed to remove trusted key {}: {}", key_id, e))?;
+    log::info!("Removed trusted key: {}", key_id);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{Signer, SigningKey};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn temp_dir(label: &str) -> PathBuf {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
+        let dir = std::env::temp_dir().join(format!("kpm-sign-test-{label}-{nanos}"));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn signing_key(seed: u8) -> SigningKey {
+        SigningKey::from_bytes(&[seed; 32])
+    }
+
+    fn write_module(dir: &Path, contents: &[u8]) -> PathBuf {
+        let path = dir.join("test.kpm");
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn sign_module(module_path: &Path, key: &SigningKey) {
+        let digest = hash_module(module_path).unwrap();
+        let signature = key.sign(&digest);
+        fs::write(format!("{}.sig", module_path.display()), signature.to_bytes()).unwrap();
+    }
+
+    fn write_trusted_key(keys_dir: &Path, key_id: &str, key: &SigningKey) {
+        fs::write(keys_dir.join(format!("{key_id}.pub")), key.verifying_key().to_bytes()).unwrap();
+    }
+
+    #[test]
+    fn trusts_a_validly_signed_module() {
+        let dir = temp_dir("trust");
+        let keys_dir = dir.join("keys");
+        fs::create_dir_all(&keys_dir).unwrap();
+
+        let key = signing_key(1);
+        let module = write_module(&dir, b"module bytes");
+        sign_module(&module, &key);
+        write_trusted_key(&keys_dir, "vendor", &key);
+
+        match verify_kpm_against(&module, &keys_dir).unwrap() {
+            VerifyOutcome::Trusted { key_id } => assert_eq!(key_id, "vendor"),
+            _ => panic!("expected Trusted outcome"),
+        }
+    }
+
+    #[test]
+    fn rejects_a_tampered_module() {
+        let dir = temp_dir("tamper");
+        let keys_dir = dir.join("keys");
+        fs::create_dir_all(&keys_dir).unwrap();
+
+        let key = signing_key(2);
+        let module = write_module(&dir, b"module bytes");
+        sign_module(&module, &key);
+        write_trusted_key(&keys_dir, "vendor", &key);
+
+        // 签名之后模块内容被篡改，摘要不再匹配
+        fs::write(&module, b"tampered bytes").unwrap();
+
+        assert!(matches!(
+            verify_kpm_against(&module, &keys_dir).unwrap(),
+            VerifyOutcome::Untrusted
+        ));
+    }
+
+    #[test]
+    fn treats_missing_signature_file_as_unsigned() {
+        let dir = temp_dir("unsigned");
+        let keys_dir = dir.join("keys");
+        fs::create_dir_all(&keys_dir).unwrap();
+
+        let module = write_module(&dir, b"module bytes");
+
+        assert!(matches!(
+            verify_kpm_against(&module, &keys_dir).unwrap(),
+            VerifyOutcome::Unsigned
+        ));
+    }
+
+    #[test]
+    fn rejects_a_signature_with_no_trusted_keys() {
+        let dir = temp_dir("no-keys");
+        let keys_dir = dir.join("keys");
+        fs::create_dir_all(&keys_dir).unwrap();
+
+        let key = signing_key(3);
+        let module = write_module(&dir, b"module bytes");
+        sign_module(&module, &key);
+        // 注意：没有把 key 写入 keys_dir
+
+        assert!(matches!(
+            verify_kpm_against(&module, &keys_dir).unwrap(),
+            VerifyOutcome::Untrusted
+        ));
+    }
+
+    #[test]
+    fn skips_malformed_trusted_key_bytes_without_failing() {
+        let dir = temp_dir("malformed-key");
+        let keys_dir = dir.join("keys");
+        fs::create_dir_all(&keys_dir).unwrap();
+
+        fs::write(keys_dir.join("broken.pub"), b"not a valid key").unwrap();
+        let good_key = signing_key(4);
+        write_trusted_key(&keys_dir, "good", &good_key);
+
+        let keys = list_trusted_key_material_in(&keys_dir).unwrap();
+        assert_eq!(keys.len(), 1);
+        assert_eq!(keys[0].0, "good");
+    }
+}